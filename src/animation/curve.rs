@@ -115,6 +115,9 @@ impl AnimationCurve {
     /// combines [`BOUNCE_IN`](AnimationCurve::BOUNCE_IN) and [`BOUNCE_OUT`](AnimationCurve::BOUNCE_OUT)
     pub const BOUNCE_IN_OUT: Self = Self::Function(|t|  combine_in_out_rev(bounce, t));
 
+    /// Under-damped spring, overshooting its end and settling with a few small oscillations.
+    pub const SPRING: Self = Self::Function(spring);
+
     /// Create a Cubic Bezier curve.
     pub const fn cubic(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
         Self::CubicBezier(CubicBezierAnimationCurve { x1, y1, x2, y2 })
@@ -224,6 +227,22 @@ fn ease_out_elastic(t: f64) -> f64 {
     }
 }
 
+fn spring(t: f64) -> f64 {
+    // damping ratio and angular frequency, settled at t = 1
+    let zeta = 0.5;
+    let omega = 12.0;
+    let omega_d = omega * (1.0 - zeta * zeta).sqrt();
+
+    if t < 0.001 {
+        0.
+    } else if t > 0.999 {
+        1.
+    } else {
+        let decay = (-zeta * omega * t).exp();
+        1.0 - decay * ((omega_d * t).cos() + (zeta * omega / omega_d) * (omega_d * t).sin())
+    }
+}
+
 fn flip_curve(f: fn(f64) -> f64, t: f64) -> f64 {
     1.0 - f(1.0 - t)
 }
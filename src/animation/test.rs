@@ -61,6 +61,15 @@ fn test_animator() {
     assert_eq!((None, Some(0.1)), advance(&mut animator, 10.));
 }
 
+#[test]
+fn test_spring_curve() {
+    let curve = AnimationCurve::SPRING;
+    assert_eq!(curve.translate(0.0), 0.0);
+    assert_eq!(curve.translate(1.0), 1.0);
+    // a spring overshoots its end value
+    assert!((0..100).any(|i| curve.translate(i as f64 / 100.0) > 1.0));
+}
+
 // Curves
 // Events
 // Loops
@@ -79,7 +79,7 @@ pub use on_cmd::OnCmd;
 pub use on_monitor::OnMonitor;
 pub use progress_bar::ProgressBar;
 pub use separator::{Orientation, Separator};
-pub use stack::{Stack, StackChildParams, StackChildPosition};
+pub use stack::{Stack, StackChildAnchor, StackChildParams, StackChildPosition};
 pub use titlebar::TitleBar;
 pub use tooltip::TooltipController;
 pub use tree::{Tree, TreeNode, TREE_NODE_REMOVE};
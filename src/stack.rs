@@ -13,9 +13,11 @@
 // limitations under the License.
 
 use druid::{
-    BoxConstraints, Data, Env, Event, EventCtx, LayoutCtx, LifeCycle, LifeCycleCtx,
-    PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget, WidgetPod, UnitPoint,
+    Affine, BoxConstraints, Data, Env, Event, EventCtx, Insets, InternalEvent, LayoutCtx,
+    LifeCycle, LifeCycleCtx, MouseEvent, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx,
+    Vec2, Widget, WidgetPod, UnitPoint,
 };
+use druid::widget::SizedBox;
 use tracing::warn;
 
 use druid::kurbo::Shape;
@@ -74,11 +76,38 @@ impl Interpolate for StackChildPosition {
 
 impl StackChildPosition {
 
+    /// Position filling the whole container (all edge distances set to zero).
+    pub const FIT: Self = Self {
+        left: Some(0.),
+        right: Some(0.),
+        top: Some(0.),
+        bottom: Some(0.),
+        width: None,
+        height: None,
+    };
+
     /// Create a new instance, all values set to `None`.
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// Fill the container minus the given insets.
+    ///
+    /// This is like [`StackChildPosition::FIT`], but keeps the
+    /// specified distance to each container edge, for example to
+    /// leave room for a toolbar band.
+    pub fn fit_with_insets(insets: impl Into<Insets>) -> Self {
+        let insets = insets.into();
+        Self {
+            left: Some(insets.x0),
+            right: Some(insets.x1),
+            top: Some(insets.y0),
+            bottom: Some(insets.y1),
+            width: None,
+            height: None,
+        }
+    }
+
     /// Builder-style method to set distance from left edge.
     pub fn left(mut self, value: Option<f64>) -> Self {
         self.left = value;
@@ -117,11 +146,71 @@ impl StackChildPosition {
 }
 
 type PositionCallback<T> = Box<dyn for<'a> Fn(&'a T, &Env) -> &'a StackChildPosition>;
+type TransformCallback<T> = Box<dyn Fn(&T, &Env) -> Affine>;
+type VisibilityCallback<T> = Box<dyn Fn(&T, &Env) -> bool>;
+type TransitionCallback<T> = Box<dyn Fn(&T, &Env) -> (AnimationCurve, f64)>;
+type ChildBuilder<T> = Box<dyn FnOnce() -> Box<dyn Widget<T>>>;
 
 enum Position<T> {
     None,
     Fixed(StackChildPosition),
     Dynamic(PositionCallback<T>),
+    Anchored(StackChildAnchor),
+}
+
+/// Stack child anchor
+///
+/// Positions a stack child relative to another child of the same
+/// stack, for example a toolbar attached above a window or a badge
+/// pinned to its corner. The anchored child follows automatically
+/// when the anchor moves or resizes.
+///
+/// The point `child_point` of the anchored child is placed at the
+/// point `anchor_point` of the anchor child, plus `offset`. The
+/// anchored child gets its natural size, limited to the size of the
+/// stack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackChildAnchor {
+    /// Index of the anchor child (in insertion order).
+    pub child: usize,
+    /// Point on the anchor child.
+    pub anchor_point: UnitPoint,
+    /// Point on the anchored child.
+    pub child_point: UnitPoint,
+    /// Additional offset.
+    pub offset: Vec2,
+}
+
+impl StackChildAnchor {
+
+    /// Anchor to the child with the given index (in insertion
+    /// order). Both points are set to [UnitPoint::TOP_LEFT].
+    pub fn new(child: usize) -> Self {
+        Self {
+            child,
+            anchor_point: UnitPoint::TOP_LEFT,
+            child_point: UnitPoint::TOP_LEFT,
+            offset: Vec2::ZERO,
+        }
+    }
+
+    /// Builder-style method to set the point on the anchor child.
+    pub fn anchor_point(mut self, point: UnitPoint) -> Self {
+        self.anchor_point = point;
+        self
+    }
+
+    /// Builder-style method to set the point on the anchored child.
+    pub fn child_point(mut self, point: UnitPoint) -> Self {
+        self.child_point = point;
+        self
+    }
+
+    /// Builder-style method to set the additional offset.
+    pub fn offset(mut self, offset: impl Into<Vec2>) -> Self {
+        self.offset = offset.into();
+        self
+    }
 }
 
 /// Stack child configuration
@@ -131,6 +220,14 @@ enum Position<T> {
 /// positioned children.
 pub struct StackChildParams<T> {
     position: Position<T>,
+    min_size: Size,
+    max_size: Size,
+    aspect_ratio: Option<f64>,
+    transform: Affine,
+    dynamic_transform: Option<TransformCallback<T>>,
+    visibility: Option<VisibilityCallback<T>>,
+    hit_test: bool,
+    transition: Option<TransitionCallback<T>>,
     // We also store the animation state here - just to keep it simple
     animated_position: Animated<StackChildPosition>,
 }
@@ -141,35 +238,65 @@ impl <T> From<StackChildPosition> for StackChildParams<T> {
     }
 }
 
+impl <T> From<StackChildAnchor> for StackChildParams<T> {
+    fn from(anchor: StackChildAnchor) -> Self {
+        StackChildParams::anchored(anchor)
+    }
+}
+
 impl <T> StackChildParams<T> {
 
-    // setup for non-positioned children
-    fn new() -> Self {
+    fn with_position(position: Position<T>) -> Self {
         Self {
-            position: Position::None,
-            animated_position: Animated::jump(StackChildPosition::new()).layout(true)
+            position,
+            min_size: Size::ZERO,
+            max_size: Size::new(f64::INFINITY, f64::INFINITY),
+            aspect_ratio: None,
+            transform: Affine::IDENTITY,
+            dynamic_transform: None,
+            visibility: None,
+            hit_test: true,
+            transition: None,
+            animated_position: Animated::jump(StackChildPosition::new()).layout(true),
         }
     }
 
+    // setup for non-positioned children
+    fn new() -> Self {
+        Self::with_position(Position::None)
+    }
+
     /// Create a *positioned* stack child
     pub fn fixed(position: StackChildPosition) -> Self {
-        Self {
-            position: Position::Fixed(position),
-            animated_position: Animated::jump(StackChildPosition::new()).layout(true)
-        }
+        Self::with_position(Position::Fixed(position))
+    }
+
+    /// Create a stack child positioned relative to another child
+    ///
+    /// Anchored children are layed-out after all other children,
+    /// each one after its anchor. The anchor's transform is taken
+    /// into account. If the anchor does not exist, a warning is
+    /// logged and the child is positioned relative to the stack
+    /// origin. If the anchors form a cycle, this happens to a single
+    /// child of the cycle to break it; the other children of the
+    /// cycle still follow their anchors.
+    ///
+    /// The anchor should be added before the anchored child, so
+    /// that it is painted below it.
+    pub fn anchored(anchor: StackChildAnchor) -> Self {
+        Self::with_position(Position::Anchored(anchor))
     }
 
     /// Create a dynamically *positioned* stack child
     pub fn dynamic<F>(position: F) -> Self
     where F: 'static + for<'a> Fn(&'a T, &Env) -> &'a StackChildPosition
     {
-        Self {
-            position: Position::Dynamic(Box::new(position)),
-            animated_position: Animated::new(StackChildPosition::new())
-                .curve(AnimationCurve::EASE_OUT)
-                .duration(0.3)
-                .layout(true),
-        }
+        let mut params = Self::with_position(Position::Dynamic(Box::new(position)));
+        params.animated_position = Animated::new(StackChildPosition::new())
+            .curve(AnimationCurve::EASE_OUT)
+            .duration(0.3)
+            .layout(true);
+        params
     }
 
     /// Builder-style method for specifying the [`AnimationCurve`].
@@ -190,6 +317,22 @@ impl <T> StackChildParams<T> {
         self.animated_position.set_curve(curve);
     }
 
+    /// Builder-style method for choosing curve and duration per transition.
+    ///
+    /// The callback is called with the new data whenever a
+    /// dynamically positioned child starts moving to a new position,
+    /// and returns the [`AnimationCurve`] and duration (in seconds)
+    /// used for that move. This overrides [`curve`] and [`duration`].
+    ///
+    /// [`curve`]: #method.curve
+    /// [`duration`]: #method.duration
+    pub fn transition<F>(mut self, transition: F) -> Self
+    where F: 'static + Fn(&T, &Env) -> (AnimationCurve, f64)
+    {
+        self.transition = Some(Box::new(transition));
+        self
+    }
+
     /// Builder-style method for specifying the animation duration.
     ///
     /// For the non-builder varient, see [`set_duration`].
@@ -207,11 +350,386 @@ impl <T> StackChildParams<T> {
     pub fn set_duration(&mut self, duration: f64) {
         self.animated_position.set_duration(duration);
     }
+
+    /// Builder-style method for specifying the minimum child size.
+    ///
+    /// For the non-builder varient, see [`set_min_size`].
+    ///
+    /// [`set_min_size`]: #method.set_min_size
+    pub fn min_size(mut self, min_size: Size) -> Self {
+        self.set_min_size(min_size);
+        self
+    }
+
+    /// Set the minimum child size.
+    ///
+    /// The size constraints computed from the [`StackChildPosition`]
+    /// are never allowed to go below this value.
+    pub fn set_min_size(&mut self, min_size: Size) {
+        self.min_size = min_size;
+    }
+
+    /// Builder-style method for specifying the maximum child size.
+    ///
+    /// For the non-builder varient, see [`set_max_size`].
+    ///
+    /// [`set_max_size`]: #method.set_max_size
+    pub fn max_size(mut self, max_size: Size) -> Self {
+        self.set_max_size(max_size);
+        self
+    }
+
+    /// Set the maximum child size.
+    ///
+    /// The size constraints computed from the [`StackChildPosition`]
+    /// are never allowed to exceed this value. If the minimum size
+    /// is larger, the minimum size wins.
+    pub fn set_max_size(&mut self, max_size: Size) {
+        self.max_size = max_size;
+    }
+
+    /// Builder-style method for specifying the aspect ratio.
+    ///
+    /// For the non-builder varient, see [`set_aspect_ratio`].
+    ///
+    /// [`set_aspect_ratio`]: #method.set_aspect_ratio
+    pub fn aspect_ratio(mut self, ratio: impl Into<Option<f64>>) -> Self {
+        self.set_aspect_ratio(ratio);
+        self
+    }
+
+    /// Set the aspect ratio (`width / height`), or `None` to disable it.
+    ///
+    /// Positioned children with an aspect ratio get the largest size
+    /// with that ratio which fits into the space computed from the
+    /// [`StackChildPosition`] and the size limits. If the ratio
+    /// conflicts with the size limits, the size limits win. Children
+    /// smaller than a slot fixed on both edges are aligned within the
+    /// slot (see [`Stack::align`]).
+    ///
+    /// Ratios which are not finite and positive are ignored.
+    pub fn set_aspect_ratio(&mut self, ratio: impl Into<Option<f64>>) {
+        self.aspect_ratio = ratio.into().filter(|ratio| ratio.is_finite() && *ratio > 0.);
+    }
+
+    /// Builder-style method for specifying the child transform.
+    ///
+    /// For the non-builder varient, see [`set_transform`].
+    ///
+    /// [`set_transform`]: #method.set_transform
+    pub fn transform(mut self, transform: Affine) -> Self {
+        self.set_transform(transform);
+        self
+    }
+
+    /// Set the child transform.
+    ///
+    /// The transform is relative to the child origin and does not
+    /// influence layout. It is applied when painting the child, and
+    /// pointer events are mapped through the inverse transform, so
+    /// hit-testing matches what is painted.
+    pub fn set_transform(&mut self, transform: Affine) {
+        self.transform = transform;
+    }
+
+    /// Builder-style method for specifying a data dependent transform.
+    ///
+    /// The callback is evaluated whenever the data changes and
+    /// replaces the transform set by [`set_transform`]. This allows
+    /// to drive open/close or drag preview effects from the data.
+    ///
+    /// [`set_transform`]: #method.set_transform
+    pub fn dynamic_transform<F>(mut self, transform: F) -> Self
+    where F: 'static + Fn(&T, &Env) -> Affine
+    {
+        self.dynamic_transform = Some(Box::new(transform));
+        self
+    }
+
+    /// Builder-style method for specifying a visibility callback.
+    ///
+    /// The callback is evaluated whenever the data changes. Hidden
+    /// children are neither painted nor hit by pointer events, and
+    /// they do not receive keyboard input, even if they have focus.
+    /// Other events (commands, timers, animation frames) are still
+    /// delivered. Lazy children (see [`Stack::add_lazy_child`]) are
+    /// built the first time the callback returns `true`.
+    pub fn visibility<F>(mut self, visibility: F) -> Self
+    where F: 'static + Fn(&T, &Env) -> bool
+    {
+        self.visibility = Some(Box::new(visibility));
+        self
+    }
+
+    /// Builder-style method for specifying the `hit_test` attribute.
+    ///
+    /// For the non-builder varient, see [`set_hit_test`].
+    ///
+    /// [`set_hit_test`]: #method.set_hit_test
+    pub fn hit_test(mut self, hit_test: bool) -> Self {
+        self.set_hit_test(hit_test);
+        self
+    }
+
+    /// Set the `hit_test` attribute (default `true`).
+    ///
+    /// Children with `hit_test` set to `false` never consume pointer
+    /// events, which are passed on to the children below instead.
+    pub fn set_hit_test(&mut self, hit_test: bool) {
+        self.hit_test = hit_test;
+    }
+
+    // Constraints for the given size range, with the per-child size
+    // limits and aspect ratio applied.
+    fn constraints(&self, min: Size, max: Size) -> BoxConstraints {
+        let min_width = min.width.min(self.max_size.width).max(self.min_size.width);
+        let max_width = max.width.min(self.max_size.width).max(min_width);
+        let min_height = min.height.min(self.max_size.height).max(self.min_size.height);
+        let max_height = max.height.min(self.max_size.height).max(min_height);
+
+        let bc = BoxConstraints::new(
+            Size::new(min_width, min_height),
+            Size::new(max_width, max_height),
+        );
+
+        match self.aspect_ratio.and_then(|ratio| aspect_ratio_size(&bc, ratio)) {
+            Some(size) => {
+                // the size limits take priority over the aspect ratio
+                let width = size.width.min(self.max_size.width).max(self.min_size.width);
+                let height = size.height.min(self.max_size.height).max(self.min_size.height);
+                BoxConstraints::tight(Size::new(width, height))
+            }
+            None => bc,
+        }
+    }
+
+    // Re-evaluate the dynamic transform, returns `true` if it changed.
+    fn update_transform(&mut self, data: &T, env: &Env) -> bool {
+        if let Some(transform_cb) = &self.dynamic_transform {
+            let transform = transform_cb(data, env);
+            if transform != self.transform {
+                self.transform = transform;
+                return true;
+            }
+        }
+        false
+    }
+
+    // transform in container coordinates for a child at `origin`
+    fn child_transform(&self, origin: Point) -> Affine {
+        let offset = origin.to_vec2();
+        Affine::translate(offset) * self.transform * Affine::translate(-offset)
+    }
+}
+
+// Compute the size with the given aspect ratio, fitting into the
+// maximum size (or the minimum size if the maximum is unbounded).
+// Returns `None` if both dimensions are unbounded.
+fn aspect_ratio_size(bc: &BoxConstraints, ratio: f64) -> Option<Size> {
+    let bounded = |max: f64, min: f64| {
+        if max.is_finite() {
+            Some(max)
+        } else if min > 0. {
+            Some(min)
+        } else {
+            None
+        }
+    };
+    let width = bounded(bc.max().width, bc.min().width);
+    let height = bounded(bc.max().height, bc.min().height);
+
+    match (width, height) {
+        (Some(width), Some(height)) => {
+            if width / ratio > height {
+                Some(Size::new(height * ratio, height))
+            } else {
+                Some(Size::new(width, width / ratio))
+            }
+        }
+        (Some(width), None) => Some(Size::new(width, width / ratio)),
+        (None, Some(height)) => Some(Size::new(height * ratio, height)),
+        (None, None) => None,
+    }
+}
+
+// Index of the topmost child whose hit area (see
+// `StackChild::hit_area`) contains `pos`.
+fn topmost_hit<I>(mut areas: I, pos: Point) -> Option<usize>
+where I: DoubleEndedIterator<Item = Option<(Rect, Affine)>> + ExactSizeIterator
+{
+    areas.rposition(|area| matches!(area, Some((rect, inverse)) if rect.winding(inverse * pos) != 0))
+}
+
+// Layout order for anchored children, so that every anchor is layed
+// out before the children anchored to it. `anchors` holds the anchor
+// index of each anchored child (`None` for other children). Returns
+// `(index, valid)` pairs, where `valid` is `false` if the anchor does
+// not exist or is part of a cycle.
+fn anchored_layout_order(anchors: &[Option<usize>]) -> Vec<(usize, bool)> {
+    let mut visiting = vec![false; anchors.len()];
+    let mut done = vec![false; anchors.len()];
+    let mut order = Vec::new();
+    for index in 0..anchors.len() {
+        visit_anchor(index, anchors, &mut visiting, &mut done, &mut order);
+    }
+    order
+}
+
+fn visit_anchor(
+    index: usize,
+    anchors: &[Option<usize>],
+    visiting: &mut [bool],
+    done: &mut [bool],
+    order: &mut Vec<(usize, bool)>,
+) {
+    if done[index] || visiting[index] {
+        return;
+    }
+    let anchor = match anchors[index] {
+        Some(anchor) => anchor,
+        None => {
+            done[index] = true;
+            return;
+        }
+    };
+
+    visiting[index] = true;
+    if anchor < anchors.len() {
+        visit_anchor(anchor, anchors, visiting, done, order);
+    }
+    // for cycles, the anchor is still being visited
+    let valid = anchor < anchors.len() && anchor != index && done[anchor];
+    visiting[index] = false;
+    done[index] = true;
+    order.push((index, valid));
+}
+
+// Origin of a positioned child of `child_size` in a stack of
+// `stack_size`. Children that do not fill a slot fixed on both edges
+// (e.g. because of their aspect ratio) are aligned within the slot.
+fn positioned_origin(position: &StackChildPosition, stack_size: Size, child_size: Size, align: UnitPoint) -> Point {
+    let slot_width = match (position.left, position.right) {
+        (Some(left), Some(right)) => stack_size.width - right - left,
+        _ => stack_size.width,
+    };
+    let slot_height = match (position.top, position.bottom) {
+        (Some(top), Some(bottom)) => stack_size.height - bottom - top,
+        _ => stack_size.height,
+    };
+    let extra_width = slot_width - child_size.width;
+    let extra_height = slot_height - child_size.height;
+    let aligned = align.resolve(Rect::new(0., 0., extra_width, extra_height)).expand();
+
+    let offset_x = match (position.left, position.right) {
+        (Some(left), Some(_)) => left + aligned.x,
+        (Some(left), None) => left,
+        (None, Some(right)) => stack_size.width - right - child_size.width,
+        (None, None) => aligned.x,
+    };
+
+    let offset_y = match (position.top, position.bottom) {
+        (Some(top), Some(_)) => top + aligned.y,
+        (Some(top), None) => top,
+        (None, Some(bottom)) => stack_size.height - bottom - child_size.height,
+        (None, None) => aligned.y,
+    };
+
+    Point::new(offset_x, offset_y)
+}
+
+// Event classification used to route events to stack children.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EventKind {
+    // pointer event, with the index of the topmost hit child
+    Pointer(Option<usize>),
+    Keyboard,
+    Other,
+}
+
+// How an event is routed to a single stack child.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Routing {
+    // deliver the event
+    Deliver,
+    // only send a MouseLeave
+    Leave,
+    // do not send anything
+    Skip,
+}
+
+// Routing rules for the child at `index`:
+//
+// - pointer events go to the topmost hit child and the hit-testable
+//   children above it; all other children only get a MouseLeave,
+//   except active children, which always get pointer events
+// - keyboard events skip hidden children
+// - all other events go to every child
+fn routing(kind: EventKind, index: usize, active: bool, visible: bool, hit_test: bool) -> Routing {
+    match kind {
+        EventKind::Pointer(hit) => {
+            if active || (visible && hit_test && index >= hit.unwrap_or(0)) {
+                Routing::Deliver
+            } else {
+                Routing::Leave
+            }
+        }
+        EventKind::Keyboard if !visible => Routing::Skip,
+        EventKind::Keyboard | EventKind::Other => Routing::Deliver,
+    }
+}
+
+// Dispatch an event according to `routes` (one per child, bottom
+// first). MouseLeave notifications are sent first, then the event
+// is delivered top-down until `send` reports it as handled.
+fn dispatch(routes: &[Routing], mut send: impl FnMut(usize, Routing) -> bool) {
+    for (index, route) in routes.iter().enumerate() {
+        if *route == Routing::Leave {
+            send(index, Routing::Leave);
+        }
+    }
+    for (index, route) in routes.iter().enumerate().rev() {
+        if *route == Routing::Deliver && send(index, Routing::Deliver) {
+            break;
+        }
+    }
+}
+
+// Map pointer event positions through `transform`. Returns `None` if
+// the event is not a pointer event or the transform is the identity.
+fn transform_mouse_event(event: &Event, transform: Affine) -> Option<Event> {
+    if transform == Affine::IDENTITY {
+        return None;
+    }
+    let map = |mouse_event: &MouseEvent| {
+        let mut mouse_event = mouse_event.clone();
+        mouse_event.pos = transform * mouse_event.pos;
+        mouse_event
+    };
+    match event {
+        Event::MouseMove(mouse_event) => Some(Event::MouseMove(map(mouse_event))),
+        Event::MouseDown(mouse_event) => Some(Event::MouseDown(map(mouse_event))),
+        Event::MouseUp(mouse_event) => Some(Event::MouseUp(map(mouse_event))),
+        Event::Wheel(mouse_event) => Some(Event::Wheel(map(mouse_event))),
+        _ => None,
+    }
+}
+
+// Whether a child laid out with `last_bc` (`None` if never laid out,
+// e.g. a freshly built lazy child) must be laid out again for `bc`.
+fn needs_layout(layout_requested: bool, last_bc: Option<&BoxConstraints>, bc: &BoxConstraints) -> bool {
+    layout_requested || last_bc != Some(bc)
 }
 
 struct StackChild<T> {
     widget: WidgetPod<T, Box<dyn Widget<T>>>,
     params: StackChildParams<T>,
+    // constraints used for the last layout pass
+    last_bc: Option<BoxConstraints>,
+    // pending builder for lazy children, taken when first visible
+    builder: Option<ChildBuilder<T>>,
+    visible: bool,
+    // an invalid or cyclic anchor has been reported
+    anchor_warned: bool,
 }
 
 impl <T: Data> StackChild<T> {
@@ -219,8 +737,78 @@ impl <T: Data> StackChild<T> {
         Self {
             widget: WidgetPod::new(Box::new(widget)),
             params,
+            last_bc: None,
+            builder: None,
+            visible: true,
+            anchor_warned: false,
         }
     }
+
+    pub fn lazy(builder: ChildBuilder<T>, params: StackChildParams<T>) -> Self {
+        let mut child = Self::new(SizedBox::empty(), params);
+        child.builder = Some(builder);
+        child
+    }
+
+    fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    // Invariant: the invalid region covers everything a child paints
+    // differently. druid records paint requests of a child in its
+    // untransformed layout rect, so the stack (whose paint insets
+    // cover all transformed children) is repainted as a whole after
+    // passing anything to a visible, transformed child.
+    fn needs_full_repaint(&self) -> bool {
+        self.is_visible() && self.params.transform != Affine::IDENTITY
+    }
+
+    fn is_hit_testable(&self) -> bool {
+        self.is_visible() && self.params.hit_test
+    }
+
+    // Layout rect and the transform mapping container coordinates to
+    // untransformed child coordinates, or `None` if not hit-testable.
+    fn hit_area(&self) -> Option<(Rect, Affine)> {
+        if !self.is_hit_testable() {
+            return None;
+        }
+        let rect = self.widget.layout_rect();
+        Some((rect, self.params.child_transform(rect.origin()).inverse()))
+    }
+
+    fn transform_pointer_event(&self, event: &Event) -> Option<Event> {
+        let transform = self.params.child_transform(self.widget.layout_rect().origin());
+        transform_mouse_event(event, transform.inverse())
+    }
+
+    // Re-evaluate the visibility callback. Builds a lazy child the
+    // first time it becomes visible and returns `true` in that case.
+    fn update_visibility(&mut self, data: &T, env: &Env) -> bool {
+        if let Some(visibility) = &self.params.visibility {
+            self.visible = visibility(data, env);
+        }
+        if self.visible {
+            if let Some(builder) = self.builder.take() {
+                self.widget = WidgetPod::new(builder());
+                self.last_bc = None;
+                return true;
+            }
+        }
+        false
+    }
+
+    // Layout the child, but skip the layout pass if neither the
+    // constraints changed nor the child requested a new layout.
+    fn layout(&mut self, ctx: &mut LayoutCtx<'_, '_>, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        // the placeholder of an unbuilt lazy child takes no space
+        let bc = if self.builder.is_some() { BoxConstraints::tight(Size::ZERO) } else { *bc };
+        if !needs_layout(self.widget.layout_requested(), self.last_bc.as_ref(), &bc) {
+            return self.widget.layout_rect().size();
+        }
+        self.last_bc = Some(bc);
+        self.widget.layout(ctx, &bc, data, env)
+    }
 }
 
 /// Stack of widgets
@@ -236,6 +824,12 @@ impl <T: Data> StackChild<T> {
 /// *Positioned* children are layed-out after *non-positioned*
 /// children. Their position is relative to the container edges (see
 /// [`StackChildPosition`]).
+///
+/// Children added later are painted on top. Pointer events are
+/// routed top-down: the topmost visible child under the pointer
+/// consumes the event, and children below it only receive a mouse
+/// leave notification (unless they are active). Children can opt out
+/// of hit-testing with [`StackChildParams::hit_test`].
 pub struct Stack<T> {
     children: Vec<StackChild<T>>,
     align: UnitPoint,
@@ -333,28 +927,70 @@ impl <T: Data> Stack<T> {
         let child = StackChild::new(child, params.into());
         self.children.push(child);
     }
+
+    /// Builder-style variant of `add_lazy_child`.
+    pub fn with_lazy_child<W: Widget<T> + 'static>(
+        mut self,
+        builder: impl FnOnce() -> W + 'static,
+        params: impl Into<StackChildParams<T>>,
+    ) -> Self {
+        self.add_lazy_child(builder, params);
+        self
+    }
+
+    /// Add a *positioned* child which is constructed on demand.
+    ///
+    /// The `builder` is called the first time the child is visible
+    /// (see [`StackChildParams::visibility`]). Until then, the child
+    /// is laid out with zero size, whatever its position, and is not
+    /// painted. Children anchored to it attach to a single point.
+    pub fn add_lazy_child<W: Widget<T> + 'static>(
+        &mut self,
+        builder: impl FnOnce() -> W + 'static,
+        params: impl Into<StackChildParams<T>>,
+    ) {
+        let builder: ChildBuilder<T> = Box::new(move || Box::new(builder()));
+        let child = StackChild::lazy(builder, params.into());
+        self.children.push(child);
+    }
 }
 
 impl<T: Data> Widget<T> for Stack<T> {
     fn event(&mut self, ctx: &mut EventCtx<'_, '_>, event: &Event, data: &mut T, env: &Env) {
-        for child in self.children.iter_mut().rev() {
-            if ctx.is_handled() {
-                return;
+        let kind = match event {
+            Event::MouseMove(mouse_event) | Event::MouseDown(mouse_event) |
+            Event::MouseUp(mouse_event) | Event::Wheel(mouse_event) => {
+                let areas = self.children.iter().map(StackChild::hit_area);
+                EventKind::Pointer(topmost_hit(areas, mouse_event.pos))
             }
+            Event::KeyDown(_) | Event::KeyUp(_) | Event::Paste(_) => EventKind::Keyboard,
+            _ => EventKind::Other,
+        };
 
-            let rect = child.widget.layout_rect();
-            let pos_match = match event {
-                Event::MouseMove(mouse_event) | Event::MouseDown(mouse_event) |
-                Event::MouseUp(mouse_event) | Event::Wheel(mouse_event) => {
-                    rect.winding(mouse_event.pos) != 0
+        let routes: Vec<Routing> = self.children.iter().enumerate()
+            .map(|(index, child)| {
+                routing(kind, index, child.widget.is_active(), child.is_visible(), child.params.hit_test)
+            })
+            .collect();
+
+        let leave = Event::Internal(InternalEvent::MouseLeave);
+        let children = &mut self.children;
+        let mut full_repaint = false;
+        dispatch(&routes, |index, route| {
+            let child = &mut children[index];
+            full_repaint |= route != Routing::Skip && child.needs_full_repaint();
+            match route {
+                Routing::Leave => child.widget.event(ctx, &leave, data, env),
+                Routing::Deliver => {
+                    let child_event = child.transform_pointer_event(event);
+                    child.widget.event(ctx, child_event.as_ref().unwrap_or(event), data, env);
                 }
-                _ => false,
-            };
-
-            child.widget.event(ctx, event, data, env);
-
-            // only send to one widget (top widget)
-            if pos_match { break; }
+                Routing::Skip => {}
+            }
+            ctx.is_handled()
+        });
+        if full_repaint {
+            ctx.request_paint();
         }
 
         if let Event::AnimFrame(nanos) = event {
@@ -368,7 +1004,14 @@ impl<T: Data> Widget<T> for Stack<T> {
 
     fn lifecycle(&mut self, ctx: &mut LifeCycleCtx<'_, '_>, event: &LifeCycle, data: &T, env: &Env) {
         for child in &mut self.children {
+            if let LifeCycle::WidgetAdded = event {
+                child.update_visibility(data, env);
+                child.params.update_transform(data, env);
+            }
             child.widget.lifecycle(ctx, event, data, env);
+            if child.needs_full_repaint() {
+                ctx.request_paint();
+            }
             if let LifeCycle::WidgetAdded = event {
                 if let Position::Dynamic(position_cb) = &child.params.position {
                     child.params.animated_position.jump_to_value(position_cb(data, env).clone());
@@ -379,12 +1022,36 @@ impl<T: Data> Widget<T> for Stack<T> {
 
     fn update(&mut self, ctx: &mut UpdateCtx<'_, '_>, _old_data: &T, data: &T, env: &Env) {
         for child in &mut self.children {
-            child.widget.update(ctx, data, env);
+            let was_visible = child.visible;
+            if child.update_visibility(data, env) {
+                // the new widget gets initialized by WidgetAdded
+                ctx.children_changed();
+            } else {
+                child.widget.update(ctx, data, env);
+                if child.needs_full_repaint() {
+                    ctx.request_paint();
+                }
+            }
+            let transform_changed = child.params.update_transform(data, env);
+            if transform_changed {
+                // paint insets and anchored children follow the transform
+                ctx.request_layout();
+            }
+            if transform_changed || child.visible != was_visible {
+                ctx.request_paint();
+            }
             // update position for dynamic children
             if let Position::Dynamic(position_cb) = &child.params.position {
                 let new_position = position_cb(data, env);
                 if new_position != &child.params.animated_position.end() {
-                    child.params.animated_position.animate(ctx, new_position.clone());
+                    let animated_position = &mut child.params.animated_position;
+                    match &child.params.transition {
+                        Some(transition) => {
+                            let (curve, duration) = transition(data, env);
+                            animated_position.animate_with(ctx, new_position.clone(), duration, curve);
+                        }
+                        None => animated_position.animate(ctx, new_position.clone()),
+                    }
                 }
             };
         }
@@ -399,7 +1066,7 @@ impl<T: Data> Widget<T> for Stack<T> {
         let mut stack_height = 0f64;
         for child in &mut self.children {
             if !matches!(child.params.position, Position::None) { continue; }
-            let child_size = child.widget.layout(ctx, &child_bc, data, env);
+            let child_size = child.layout(ctx, &child_bc, data, env);
             stack_width = stack_width.max(child_size.width);
             stack_height = stack_height.max(child_size.height);
             child.widget.set_origin(ctx, data, env, Point::ORIGIN);
@@ -411,7 +1078,7 @@ impl<T: Data> Widget<T> for Stack<T> {
         for child in &mut self.children {
             let animated_position = child.params.animated_position.get();
             let position = match &child.params.position {
-                Position::None => continue,
+                Position::None | Position::Anchored(_) => continue,
                 Position::Fixed(position) => position,
                 Position::Dynamic(_) => &animated_position,
             };
@@ -454,36 +1121,64 @@ impl<T: Data> Widget<T> for Stack<T> {
                 _ => { /* no height constraint */ }
             }
 
-            let child_bc = BoxConstraints::new(
+            let child_bc = child.params.constraints(
                 Size::new(min_width, min_height),
                 Size::new(max_width, max_height),
             );
 
-            let child_size = child.widget.layout(ctx, &child_bc, data, env);
-            let align = self.align;
+            let child_size = child.layout(ctx, &child_bc, data, env);
+            let origin = positioned_origin(position, size, child_size, self.align);
+            child.widget.set_origin(ctx, data, env, origin);
+        }
 
-            let offset_x = match (position.left, position.right) {
-                (Some(left), _) => left,
-                (None, Some(right)) => stack_width - right - child_size.width,
-                (None, None) => {
-                    let extra_width = stack_width - child_size.width;
-                    align.resolve(Rect::new(0., 0., extra_width, 0.)).expand().x
-                }
+        // Compute size for anchored children, after their anchors
+        let anchors: Vec<Option<usize>> = self.children.iter()
+            .map(|child| match &child.params.position {
+                Position::Anchored(anchor) => Some(anchor.child),
+                _ => None,
+            })
+            .collect();
+
+        for (index, valid) in anchored_layout_order(&anchors) {
+            let anchor = match &self.children[index].params.position {
+                Position::Anchored(anchor) => anchor.clone(),
+                _ => continue,
             };
 
-            let offset_y = match (position.top, position.bottom) {
-                (Some(top), _) => top,
-                (None, Some(bottom)) => stack_height - bottom - child_size.height,
-                (None, None) => {
-                    let extra_height = stack_height - child_size.height;
-                    align.resolve(Rect::new(0., 0., 0., extra_height)).expand().y
+            let anchor_rect = if valid {
+                let anchor_child = &self.children[anchor.child];
+                let rect = anchor_child.widget.layout_rect();
+                anchor_child.params.child_transform(rect.origin()).transform_rect_bbox(rect)
+            } else {
+                // anchors never change, so report them only once
+                let child = &mut self.children[index];
+                if !child.anchor_warned {
+                    warn!("stack child {} has an invalid or cyclic anchor {}", index, anchor.child);
+                    child.anchor_warned = true;
                 }
+                Rect::ZERO
             };
 
-            let origin = Point::new(offset_x, offset_y);
+            let child = &mut self.children[index];
+            let child_bc = child.params.constraints(Size::ZERO, size);
+            let child_size = child.layout(ctx, &child_bc, data, env);
+
+            let child_point = anchor.child_point.resolve(child_size.to_rect()).to_vec2();
+            let origin = anchor.anchor_point.resolve(anchor_rect) + anchor.offset - child_point;
             child.widget.set_origin(ctx, data, env, origin);
         }
 
+        // transformed children may paint outside of the stack
+        let mut paint_rect = size.to_rect();
+        if !self.clip {
+            for child in &self.children {
+                let origin = child.widget.layout_rect().origin();
+                let child_rect = child.params.child_transform(origin).transform_rect_bbox(child.widget.paint_rect());
+                paint_rect = paint_rect.union(child_rect);
+            }
+        }
+        ctx.set_paint_insets(paint_rect - size.to_rect());
+
         size
     }
 
@@ -494,7 +1189,237 @@ impl<T: Data> Widget<T> for Stack<T> {
             ctx.clip(size.to_rect());
         }
         for child in &mut self.children {
-            child.widget.paint(ctx, data, env);
+            if !child.is_visible() { continue; }
+            let transform = child.params.child_transform(child.widget.layout_rect().origin());
+            if transform == Affine::IDENTITY {
+                child.widget.paint(ctx, data, env);
+            } else {
+                // the paint region is not transformed, so paint unconditionally
+                ctx.with_save(|ctx| {
+                    ctx.transform(transform);
+                    child.widget.paint_always(ctx, data, env);
+                });
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn unbounded() -> Size {
+        Size::new(f64::INFINITY, f64::INFINITY)
+    }
+
+    fn fixed_params() -> StackChildParams<()> {
+        StackChildParams::fixed(StackChildPosition::new())
+    }
+
+    #[test]
+    fn test_constraints_size_limits() {
+        let params = fixed_params().min_size(Size::new(50., 50.)).max_size(Size::new(100., 100.));
+        let bc = params.constraints(Size::ZERO, unbounded());
+        assert_eq!(bc.min(), Size::new(50., 50.));
+        assert_eq!(bc.max(), Size::new(100., 100.));
+
+        // tight constraints from the position are clamped as well
+        let bc = params.constraints(Size::new(300., 10.), Size::new(300., 10.));
+        assert_eq!(bc, BoxConstraints::tight(Size::new(100., 50.)));
+    }
+
+    #[test]
+    fn test_constraints_min_size_wins() {
+        let params = fixed_params().min_size(Size::new(200., 0.)).max_size(Size::new(100., 100.));
+        let bc = params.constraints(Size::ZERO, unbounded());
+        assert_eq!(bc.min(), Size::new(200., 0.));
+        assert_eq!(bc.max(), Size::new(200., 100.));
+    }
+
+    #[test]
+    fn test_aspect_ratio_size() {
+        let loose = |width, height| BoxConstraints::new(Size::ZERO, Size::new(width, height));
+
+        assert_eq!(aspect_ratio_size(&loose(100., 100.), 2.), Some(Size::new(100., 50.)));
+        assert_eq!(aspect_ratio_size(&loose(100., 100.), 0.5), Some(Size::new(50., 100.)));
+        assert_eq!(aspect_ratio_size(&loose(f64::INFINITY, 40.), 2.), Some(Size::new(80., 40.)));
+        assert_eq!(aspect_ratio_size(&loose(40., f64::INFINITY), 2.), Some(Size::new(40., 20.)));
+        assert_eq!(aspect_ratio_size(&loose(f64::INFINITY, f64::INFINITY), 2.), None);
+
+        // unbounded maximum falls back to the minimum
+        let bc = BoxConstraints::new(Size::new(30., 0.), unbounded());
+        assert_eq!(aspect_ratio_size(&bc, 3.), Some(Size::new(30., 10.)));
+    }
+
+    #[test]
+    fn test_constraints_aspect_ratio() {
+        let params = fixed_params().aspect_ratio(2.);
+        let bc = params.constraints(Size::new(100., 100.), Size::new(100., 100.));
+        assert_eq!(bc, BoxConstraints::tight(Size::new(100., 50.)));
+
+        // the size limits win over the aspect ratio
+        let params = fixed_params().aspect_ratio(1.).min_size(Size::new(200., 0.));
+        let bc = params.constraints(Size::new(100., 100.), Size::new(100., 100.));
+        assert_eq!(bc, BoxConstraints::tight(Size::new(200., 100.)));
+
+        // without any bound the constraints are left alone
+        let params = fixed_params().aspect_ratio(1.);
+        assert_eq!(params.constraints(Size::ZERO, unbounded()), BoxConstraints::UNBOUNDED);
+    }
+
+    #[test]
+    fn test_aspect_ratio_rejects_invalid() {
+        assert_eq!(fixed_params().aspect_ratio(f64::INFINITY).aspect_ratio, None);
+        assert_eq!(fixed_params().aspect_ratio(f64::NAN).aspect_ratio, None);
+        assert_eq!(fixed_params().aspect_ratio(0.).aspect_ratio, None);
+        assert_eq!(fixed_params().aspect_ratio(1.5).aspect_ratio, Some(1.5));
+    }
+
+    #[test]
+    fn test_positioned_origin() {
+        let stack_size = Size::new(100., 100.);
+        let child_size = Size::new(100., 50.);
+        let fit = StackChildPosition::FIT;
+        assert_eq!(positioned_origin(&fit, stack_size, child_size, UnitPoint::TOP_LEFT), Point::new(0., 0.));
+        assert_eq!(positioned_origin(&fit, stack_size, child_size, UnitPoint::CENTER), Point::new(0., 25.));
+        assert_eq!(positioned_origin(&fit, stack_size, child_size, UnitPoint::BOTTOM_RIGHT), Point::new(0., 50.));
+
+        // letterboxing happens within the slot
+        let inset = StackChildPosition::fit_with_insets(10.);
+        assert_eq!(positioned_origin(&inset, stack_size, Size::new(40., 80.), UnitPoint::CENTER), Point::new(30., 10.));
+
+        // single edges are not aligned
+        let edges = StackChildPosition::new().left(Some(10.)).bottom(Some(10.));
+        assert_eq!(positioned_origin(&edges, stack_size, child_size, UnitPoint::CENTER), Point::new(10., 40.));
+
+        // no edges at all align within the stack
+        let none = StackChildPosition::new();
+        assert_eq!(positioned_origin(&none, stack_size, child_size, UnitPoint::CENTER), Point::new(0., 25.));
+    }
+
+    #[test]
+    fn test_anchored_layout_order() {
+        // 0 <- 2 <- 1: child 1 is anchored to the later child 2
+        let order = anchored_layout_order(&[None, Some(2), Some(0)]);
+        assert_eq!(order, [(2, true), (1, true)]);
+    }
+
+    #[test]
+    fn test_anchored_layout_order_invalid() {
+        // self-anchored and non-existing anchors
+        assert_eq!(anchored_layout_order(&[Some(0)]), [(0, false)]);
+        assert_eq!(anchored_layout_order(&[None, Some(5)]), [(1, false)]);
+
+        // cycle 0 -> 1 -> 0, broken at the child closing the cycle
+        let order = anchored_layout_order(&[Some(1), Some(0), Some(1)]);
+        assert_eq!(order, [(1, false), (0, true), (2, true)]);
+    }
+
+    #[test]
+    fn test_pointer_routing() {
+        let kind = EventKind::Pointer(Some(1));
+
+        // below the hit: only a leave notification
+        assert_eq!(routing(kind, 0, false, true, true), Routing::Leave);
+        // the hit and hit-testable children above it get the event
+        assert_eq!(routing(kind, 1, false, true, true), Routing::Deliver);
+        assert_eq!(routing(kind, 2, false, true, true), Routing::Deliver);
+        // hidden or opted-out children are never delivered to
+        assert_eq!(routing(kind, 2, false, false, true), Routing::Leave);
+        assert_eq!(routing(kind, 2, false, true, false), Routing::Leave);
+        // active children always get pointer events
+        assert_eq!(routing(kind, 0, true, true, true), Routing::Deliver);
+        assert_eq!(routing(kind, 0, true, true, false), Routing::Deliver);
+
+        // nothing hit: every hit-testable child gets the event
+        let kind = EventKind::Pointer(None);
+        assert_eq!(routing(kind, 0, false, true, true), Routing::Deliver);
+        assert_eq!(routing(kind, 0, false, true, false), Routing::Leave);
+    }
+
+    #[test]
+    fn test_non_pointer_routing() {
+        assert_eq!(routing(EventKind::Keyboard, 0, false, true, true), Routing::Deliver);
+        assert_eq!(routing(EventKind::Keyboard, 0, false, false, true), Routing::Skip);
+        assert_eq!(routing(EventKind::Keyboard, 0, false, true, false), Routing::Deliver);
+        assert_eq!(routing(EventKind::Other, 0, false, false, false), Routing::Deliver);
+    }
+
+    #[test]
+    fn test_dispatch_order() {
+        let routes = [Routing::Deliver, Routing::Leave, Routing::Skip, Routing::Deliver];
+        let mut calls = Vec::new();
+        dispatch(&routes, |index, route| {
+            calls.push((index, route));
+            false
+        });
+        // leave notifications first, then top-down delivery
+        assert_eq!(calls, [(1, Routing::Leave), (3, Routing::Deliver), (0, Routing::Deliver)]);
+    }
+
+    #[test]
+    fn test_dispatch_stops_when_handled() {
+        let routes = [Routing::Deliver, Routing::Leave, Routing::Deliver, Routing::Deliver];
+        let mut calls = Vec::new();
+        dispatch(&routes, |index, route| {
+            calls.push((index, route));
+            index == 2
+        });
+        assert_eq!(calls, [(1, Routing::Leave), (3, Routing::Deliver), (2, Routing::Deliver)]);
+    }
+
+    #[test]
+    fn test_needs_layout() {
+        let bc = BoxConstraints::new(Size::ZERO, Size::new(100., 100.));
+        let other = BoxConstraints::tight(Size::new(100., 100.));
+        // same constraints, no layout request
+        assert!(!needs_layout(false, Some(&bc), &bc));
+        // changed constraints
+        assert!(needs_layout(false, Some(&other), &bc));
+        // layout requested by the child
+        assert!(needs_layout(true, Some(&bc), &bc));
+        // never laid out, or a lazy child was just built
+        assert!(needs_layout(false, None, &bc));
+    }
+
+    fn area(rect: Rect) -> Option<(Rect, Affine)> {
+        Some((rect, Affine::IDENTITY))
+    }
+
+    #[test]
+    fn test_topmost_hit() {
+        let areas = [
+            area(Rect::new(0., 0., 100., 100.)),
+            area(Rect::new(50., 50., 150., 150.)),
+        ];
+        let hit = |x, y| topmost_hit(areas.iter().cloned(), Point::new(x, y));
+
+        // overlapping region, the upper child wins
+        assert_eq!(hit(75., 75.), Some(1));
+        assert_eq!(hit(25., 25.), Some(0));
+        assert_eq!(hit(125., 125.), Some(1));
+        assert_eq!(hit(200., 200.), None);
+    }
+
+    #[test]
+    fn test_topmost_hit_opt_out() {
+        // the upper child does not take part in hit-testing
+        let areas = [area(Rect::new(0., 0., 100., 100.)), None];
+        assert_eq!(topmost_hit(areas.iter().cloned(), Point::new(50., 50.)), Some(0));
+    }
+
+    #[test]
+    fn test_topmost_hit_transformed() {
+        let params = StackChildParams::<()>::fixed(StackChildPosition::new())
+            .transform(Affine::scale(2.0));
+        let rect = Rect::new(10., 10., 20., 20.);
+        let inverse = params.child_transform(rect.origin()).inverse();
+        let hit = |x, y| topmost_hit(std::iter::once(Some((rect, inverse))), Point::new(x, y));
+
+        // scaled around the origin, the child covers (10, 10) - (30, 30)
+        assert_eq!(hit(25., 25.), Some(0));
+        assert_eq!(hit(15., 15.), Some(0));
+        assert_eq!(hit(5., 5.), None);
+        assert_eq!(hit(35., 35.), None);
+    }
+}